@@ -1,12 +1,15 @@
 use lazy_static::lazy_static;
 use log::info;
 use regex::Regex;
-use reqwest::blocking::{get as GET, Client, Response};
+use reqwest::{Client, Response, StatusCode};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
 
 use crate::error::{FritzError, Result};
 use crate::fritz_xml as xml;
+use crate::session_cache;
 
-// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
 /// Computes the string that we use to authenticate.
 /// 1. Replace all non-ascii chars in `password` with "."
@@ -30,35 +33,71 @@ fn request_response(password: &str, challenge: &str) -> String {
 
 const DEFAULT_SID: &str = "0000000000000000";
 
+/// Builds the single [`reqwest::Client`] shared by a [`Token`], reusing its
+/// connection pool across requests instead of paying TLS/TCP setup on every
+/// call. Gzip is enabled, and the `http2` feature lets reqwest negotiate
+/// HTTP/2 via ALPN wherever the remote speaks TLS - but a FRITZ!Box is
+/// talked to over plain HTTP, which has no ALPN, so these requests always
+/// stay on HTTP/1.1. That's expected: consumer FRITZ!OS doesn't speak h2c,
+/// so we must not force prior-knowledge HTTP/2 here.
+fn build_client() -> reqwest::Result<Client> {
+    Client::builder().cookie_store(true).gzip(true).build()
+}
+
 pub struct Token {
-    sid: String,
+    sid: RwLock<String>,
     host: String,
+    user: String,
+    /// Kept around (never logged) so `request` can transparently re-login
+    /// when the fritz box reports the session has expired.
+    password: Secret<String>,
+    client: Client,
 }
 
 /// Requests a temporary token (session id = sid) from the fritz box using user
-/// name and password.
-pub fn get_token(host: &str, user: &str, password: &str) -> Result<Token> {
+/// name and password. Reuses a cached, still-fresh sid for `host` instead of
+/// performing a full MD5 challenge-response login when possible.
+pub async fn get_token(host: &str, user: &str, password: &str) -> Result<Token> {
+    let client = build_client()?;
+
+    let sid = match session_cache::load(host, user) {
+        Some(cached) => cached.sid,
+        None => {
+            let sid = login(&client, host, user, password).await?;
+            session_cache::save(host, user, &sid)?;
+            sid
+        }
+    };
+
+    Ok(Token {
+        sid: RwLock::new(sid),
+        host: host.to_string(),
+        user: user.to_string(),
+        password: Secret::new(password.to_string()),
+        client,
+    })
+}
+
+/// Performs the MD5 challenge-response login and returns the resulting sid.
+async fn login(client: &Client, host: &str, user: &str, password: &str) -> Result<String> {
     let url = format!("http://{}/login_sid.lua", host);
-    let res: Response = GET(&url)?.error_for_status().map_err(|err| {
+    let res: Response = client.get(&url).send().await?.error_for_status().map_err(|err| {
         eprintln!("GET login_sid.lua for user {}", user);
         err
     })?;
 
-    let xml = res.text()?;
+    let xml = res.text().await?;
     let info = xml::parse_session_info(&xml)?;
     if DEFAULT_SID != info.sid {
-        return Ok(Token {
-            sid: info.sid,
-            host: host.to_string(),
-        });
+        return Ok(info.sid);
     }
     let response = request_response(password, &info.challenge);
     let url = format!(
         "http://{}/login_sid.lua?username={}&response={}",
         host, user, response
     );
-    let login: Response = GET(&url)?.error_for_status()?;
-    let info = xml::parse_session_info(&login.text()?)?;
+    let login: Response = client.get(&url).send().await?.error_for_status()?;
+    let info = xml::parse_session_info(&login.text().await?)?;
 
     if DEFAULT_SID == info.sid {
         return Err(FritzError::LoginError(
@@ -66,10 +105,16 @@ pub fn get_token(host: &str, user: &str, password: &str) -> Result<Token> {
         ));
     }
 
-    Ok(Token {
-        sid: info.sid,
-        host: host.to_string(),
-    })
+    Ok(info.sid)
+}
+
+/// Logs in again using the [`Token`]'s own host/user/password and swaps in
+/// the fresh sid, updating the on-disk cache too.
+async fn reauthenticate(token: &Token) -> Result<()> {
+    let sid = login(&token.client, &token.host, &token.user, token.password.expose_secret()).await?;
+    session_cache::save(&token.host, &token.user, &sid)?;
+    *token.sid.write().await = sid;
+    Ok(())
 }
 
 pub(crate) enum Commands {
@@ -78,14 +123,29 @@ pub(crate) enum Commands {
     // GetSwitchPower,
     // GetSwitchEnergy,
     // GetSwitchName,
-    // GetTemplateListInfos,
+    GetTemplateListInfos,
+    ApplyTemplate,
     SetSwitchOff,
     SetSwitchOn,
     SetSwitchToggle,
+    SetHkrTsoll,
+    SetHkrBoost,
+    SetHkrWindowOpen,
+}
+
+/// Sends raw HTTP requests to the fritz box, reusing the [`Token`]'s client.
+pub(crate) async fn request(cmd: Commands, token: &Token, ain: Option<&str>) -> Result<String> {
+    request_with_params(cmd, token, ain, &[]).await
 }
 
-/// Sends raw HTTP requests to the fritz box.
-pub(crate) fn request(cmd: Commands, token: &Token, ain: Option<&str>) -> Result<String> {
+/// Like [`request`], but allows passing additional `switchcmd`-specific query
+/// parameters (e.g. `tsoll` for `sethkrtsoll`).
+pub(crate) async fn request_with_params(
+    cmd: Commands,
+    token: &Token,
+    ain: Option<&str>,
+    params: &[(&str, &str)],
+) -> Result<String> {
     use Commands::*;
     let cmd = match cmd {
         GetDeviceListInfos => "getdevicelistinfos",
@@ -93,19 +153,52 @@ pub(crate) fn request(cmd: Commands, token: &Token, ain: Option<&str>) -> Result
         // GetSwitchPower => "getswitchpower",
         // GetSwitchEnergy => "getswitchenergy",
         // GetSwitchName => "getswitchname",
-        // GetTemplateListInfos => "gettemplatelistinfos",
+        GetTemplateListInfos => "gettemplatelistinfos",
+        ApplyTemplate => "applytemplate",
         SetSwitchOff => "setswitchoff",
         SetSwitchOn => "setswitchon",
         SetSwitchToggle => "setswitchtoggle",
+        SetHkrTsoll => "sethkrtsoll",
+        SetHkrBoost => "sethkrboost",
+        SetHkrWindowOpen => "sethkrwindowopen",
     };
+
+    let (status, body) = send(cmd, token, ain, params).await?;
+    if !session_expired(status, &body) {
+        return Ok(body);
+    }
+
+    info!("[fritz api] {} session expired, re-authenticating", cmd);
+    reauthenticate(token).await?;
+    let (_, body) = send(cmd, token, ain, params).await?;
+    Ok(body)
+}
+
+/// True if the fritz box's response means our sid is no longer valid: either
+/// an HTTP 403, or a body that echoes back the default, unauthenticated sid.
+fn session_expired(status: StatusCode, body: &str) -> bool {
+    status == StatusCode::FORBIDDEN || body.trim() == DEFAULT_SID
+}
+
+async fn send(
+    cmd: &str,
+    token: &Token,
+    ain: Option<&str>,
+    params: &[(&str, &str)],
+) -> Result<(StatusCode, String)> {
     let url = format!("http://{}/webservices/homeautoswitch.lua", token.host);
-    let mut client = Client::new()
+    let sid = token.sid.read().await.clone();
+    let mut req = token
+        .client
         .get(url)
-        .query(&[("switchcmd", cmd), ("sid", &token.sid)]);
+        .query(&[("switchcmd", cmd), ("sid", &sid)]);
     if let Some(ain) = ain {
-        client = client.query(&[("ain", ain)]);
+        req = req.query(&[("ain", ain)]);
+    }
+    if !params.is_empty() {
+        req = req.query(params);
     }
-    let response = client.send()?;
+    let response = req.send().await?;
     let status = response.status();
     info!(
         "[fritz api] {} status: {:?} {:?}",
@@ -114,24 +207,24 @@ pub(crate) fn request(cmd: Commands, token: &Token, ain: Option<&str>) -> Result
         status.canonical_reason().unwrap_or_default()
     );
 
-    Ok(response.text()?)
+    Ok((status, response.text().await?))
 }
 
-// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
 /// Requests & parses raw [`Device`]s.
-pub(crate) fn device_infos(token: &Token) -> Result<Vec<xml::Device>> {
-    let xml = request(Commands::GetDeviceListInfos, &token, None)?;
+pub(crate) async fn device_infos(token: &Token) -> Result<Vec<xml::Device>> {
+    let xml = request(Commands::GetDeviceListInfos, token, None).await?;
     xml::parse_device_infos(xml)
 }
 
 /// Requests & parses raw [`DeviceStats`]s.
-pub(crate) fn fetch_device_stats(ain: &str, token: &Token) -> Result<Vec<xml::DeviceStats>> {
-    let xml = request(Commands::GetBasicDeviceStats, &token, Some(ain))?;
+pub(crate) async fn fetch_device_stats(ain: &str, token: &Token) -> Result<Vec<xml::DeviceStats>> {
+    let xml = request(Commands::GetBasicDeviceStats, token, Some(ain)).await?;
     xml::parse_device_stats(xml)
 }
 
-// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
 #[cfg(test)]
 mod tests {
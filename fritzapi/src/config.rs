@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::Result;
+
+const DEFAULT_HOST: &str = "fritz.box";
+
+/// User-editable settings loaded from a TOML file, so `host`/`user`/
+/// `password` don't have to be repeated as CLI flags on every invocation.
+///
+/// Precedence (highest wins): CLI flags > config file > built-in defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    pub host: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// A default device identifier (AIN), used when a subcommand's `--ain`
+    /// flag is omitted.
+    pub ain: Option<String>,
+}
+
+impl Config {
+    /// Reads and parses `path`. A missing file is not an error - it just
+    /// yields a [`Config`] with every field unset, so callers fall back to
+    /// CLI flags or hard-coded defaults.
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// The default config file location, `~/.config/fritz-homeautomation/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("fritz-homeautomation").join("config.toml"))
+    }
+
+    /// The fritz box host to talk to, falling back to `fritz.box` if neither
+    /// the config file nor a CLI flag supplied one.
+    pub fn host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::api::{self, Commands, Token};
+use crate::error::{FritzError, Result};
+
+/// AVM encodes HKR temperatures in half-degree steps as an integer in
+/// 16..=56 (8..=28 °C), with two sentinels: 253 means "off" (valve closed)
+/// and 254 means "on" (valve fully open).
+const HKR_OFF: u8 = 253;
+const HKR_ON: u8 = 254;
+const HKR_MIN_CELSIUS: f32 = 8.0;
+const HKR_MAX_CELSIUS: f32 = 28.0;
+
+#[derive(Debug)]
+pub enum HkrTemperature {
+    Celsius(f32),
+    Off,
+    On,
+}
+
+impl HkrTemperature {
+    fn decode(raw: u8) -> HkrTemperature {
+        match raw {
+            HKR_OFF => HkrTemperature::Off,
+            HKR_ON => HkrTemperature::On,
+            raw => HkrTemperature::Celsius(raw as f32 / 2.0),
+        }
+    }
+
+    fn encode(&self) -> Result<u8> {
+        match self {
+            HkrTemperature::Off => Ok(HKR_OFF),
+            HkrTemperature::On => Ok(HKR_ON),
+            HkrTemperature::Celsius(celsius) => {
+                if !(HKR_MIN_CELSIUS..=HKR_MAX_CELSIUS).contains(celsius) {
+                    return Err(FritzError::InvalidHkrTemperature(*celsius));
+                }
+                Ok((celsius * 2.0).round() as u8)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for HkrTemperature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HkrTemperature::Celsius(celsius) => write!(f, "{:.1}°C", celsius),
+            HkrTemperature::Off => write!(f, "off"),
+            HkrTemperature::On => write!(f, "on"),
+        }
+    }
+}
+
+/// A FRITZ!DECT 3xx / Comet DECT radiator thermostat.
+#[derive(Debug)]
+pub struct FritzDectThermostat {
+    pub identifier: String,
+    pub productname: String,
+    pub name: String,
+    pub current: HkrTemperature,
+    pub target: HkrTemperature,
+    pub comfort: HkrTemperature,
+    pub economy: HkrTemperature,
+    pub battery_percent: u8,
+    pub window_open: bool,
+}
+
+impl FritzDectThermostat {
+    pub(crate) fn decode(raw: u8) -> HkrTemperature {
+        HkrTemperature::decode(raw)
+    }
+
+    /// The currently measured room temperature, if known.
+    pub fn current_celsius(&self) -> Option<f32> {
+        match self.current {
+            HkrTemperature::Celsius(celsius) => Some(celsius),
+            _ => None,
+        }
+    }
+
+    /// The target temperature the HKR is regulating towards.
+    pub fn target_celsius(&self) -> Option<f32> {
+        match self.target {
+            HkrTemperature::Celsius(celsius) => Some(celsius),
+            _ => None,
+        }
+    }
+
+    /// Sets the target temperature via `sethkrtsoll`. Pass `None` to turn the
+    /// radiator valve off.
+    pub async fn set_target_celsius(&self, token: &Token, celsius: Option<f32>) -> Result<()> {
+        let target = match celsius {
+            Some(celsius) => HkrTemperature::Celsius(celsius),
+            None => HkrTemperature::Off,
+        };
+        let raw = target.encode()?.to_string();
+        api::request_with_params(
+            Commands::SetHkrTsoll,
+            token,
+            Some(&self.identifier),
+            &[("tsoll", &raw)],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Enables or disables boost mode (full heat for two hours).
+    pub async fn set_boost(&self, token: &Token, enable: bool) -> Result<()> {
+        let endtimestamp = if enable {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            (now + 2 * 60 * 60).to_string()
+        } else {
+            "0".to_string()
+        };
+        api::request_with_params(
+            Commands::SetHkrBoost,
+            token,
+            Some(&self.identifier),
+            &[("endtimestamp", &endtimestamp)],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Enables or disables "window open" mode, which pauses heating.
+    pub async fn set_window_open(&self, token: &Token, enable: bool) -> Result<()> {
+        let endtimestamp = if enable { "4294967295" } else { "0" };
+        api::request_with_params(
+            Commands::SetHkrWindowOpen,
+            token,
+            Some(&self.identifier),
+            &[("endtimestamp", endtimestamp)],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_celsius() {
+        assert!(matches!(HkrTemperature::decode(32), HkrTemperature::Celsius(c) if c == 16.0));
+        assert!(matches!(HkrTemperature::decode(16), HkrTemperature::Celsius(c) if c == 8.0));
+        assert!(matches!(HkrTemperature::decode(56), HkrTemperature::Celsius(c) if c == 28.0));
+    }
+
+    #[test]
+    fn decode_sentinels() {
+        assert!(matches!(HkrTemperature::decode(253), HkrTemperature::Off));
+        assert!(matches!(HkrTemperature::decode(254), HkrTemperature::On));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        for raw in [16_u8, 17, 32, 56] {
+            let decoded = HkrTemperature::decode(raw);
+            assert_eq!(decoded.encode().unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn encode_sentinels() {
+        assert_eq!(HkrTemperature::Off.encode().unwrap(), HKR_OFF);
+        assert_eq!(HkrTemperature::On.encode().unwrap(), HKR_ON);
+    }
+
+    #[test]
+    fn encode_rejects_out_of_range() {
+        assert!(HkrTemperature::Celsius(7.5).encode().is_err());
+        assert!(HkrTemperature::Celsius(28.5).encode().is_err());
+        assert!(HkrTemperature::Celsius(40.0).encode().is_err());
+    }
+}
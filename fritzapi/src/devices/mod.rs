@@ -1,16 +1,37 @@
+use serde::Serialize;
+
 use crate::api::{self, Token};
 use crate::error::Result;
 use crate::fritz_xml::{self as xml, Alert};
 
 mod fritz_dect_2xx;
+mod fritz_dect_thermostat;
 pub use fritz_dect_2xx::FritzDect2XX;
+pub use fritz_dect_thermostat::{FritzDectThermostat, HkrTemperature};
 
 #[derive(Debug)]
 pub enum AVMDevice {
     FritzDect2XX(FritzDect2XX),
+    FritzDectThermostat(FritzDectThermostat),
     Other(xml::Device),
 }
 
+/// A flattened, serializable snapshot of an [`AVMDevice`], used by the CLI's
+/// `--format json`/`--format csv` output.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSnapshot {
+    pub identifier: String,
+    pub name: String,
+    pub on: bool,
+    pub watts: Option<f32>,
+    pub voltage: Option<f32>,
+    pub energy_wh: Option<u32>,
+    pub celsius: Option<f32>,
+    pub alert: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Vec<xml::DeviceStats>>,
+}
+
 impl std::fmt::Display for AVMDevice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -21,6 +42,13 @@ impl std::fmt::Display for AVMDevice {
                     dev.identifier, dev.productname, dev.name
                 )?;
             }
+            AVMDevice::FritzDectThermostat(dev @ FritzDectThermostat { .. }) => {
+                writeln!(
+                    f,
+                    "identifier={:?} productname={:?} name={:?} current={} target={}",
+                    dev.identifier, dev.productname, dev.name, dev.current, dev.target
+                )?;
+            }
             AVMDevice::Other(dev) => {
                 writeln!(
                     f,
@@ -34,8 +62,8 @@ impl std::fmt::Display for AVMDevice {
 }
 
 impl AVMDevice {
-    pub fn list(token: &Token) -> Result<Vec<AVMDevice>> {
-        let devices = api::device_infos(token)?;
+    pub async fn list(token: &Token) -> Result<Vec<AVMDevice>> {
+        let devices = api::device_infos(token).await?;
         // println!("got {} devices", devices.len());
         // for d in &devices {
         //     println!("a: {:?}", d);
@@ -77,6 +105,33 @@ impl AVMDevice {
                     })
                 }
 
+                xml::Device {
+                    identifier,
+                    productname,
+                    name,
+                    hkr:
+                        Some(xml::Hkr {
+                            tist,
+                            tsoll,
+                            komfort,
+                            absenk,
+                            battery,
+                            windowopenactiv,
+                            ..
+                        }),
+                    ..
+                } => AVMDevice::FritzDectThermostat(FritzDectThermostat {
+                    identifier: identifier.clone(),
+                    productname: productname.clone(),
+                    name: name.clone(),
+                    current: FritzDectThermostat::decode(*tist),
+                    target: FritzDectThermostat::decode(*tsoll),
+                    comfort: FritzDectThermostat::decode(*komfort),
+                    economy: FritzDectThermostat::decode(*absenk),
+                    battery_percent: *battery,
+                    window_open: *windowopenactiv,
+                }),
+
                 _ => AVMDevice::Other(dev),
             })
             .collect();
@@ -86,6 +141,7 @@ impl AVMDevice {
     pub fn id(&self) -> &str {
         match self {
             AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.identifier,
+            AVMDevice::FritzDectThermostat(dev @ FritzDectThermostat { .. }) => &dev.identifier,
             AVMDevice::Other(dev) => &dev.identifier,
         }
     }
@@ -93,6 +149,7 @@ impl AVMDevice {
     pub fn name(&self) -> &str {
         match self {
             AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.name,
+            AVMDevice::FritzDectThermostat(dev @ FritzDectThermostat { .. }) => &dev.name,
             AVMDevice::Other(dev) => &dev.name,
         }
     }
@@ -100,6 +157,7 @@ impl AVMDevice {
     pub fn productname(&self) -> &str {
         match self {
             AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.productname,
+            AVMDevice::FritzDectThermostat(dev @ FritzDectThermostat { .. }) => &dev.productname,
             AVMDevice::Other(dev) => &dev.productname,
         }
     }
@@ -107,6 +165,7 @@ impl AVMDevice {
     pub fn is_on(&self) -> bool {
         match self {
             AVMDevice::FritzDect2XX(FritzDect2XX { on, .. }) => *on,
+            AVMDevice::FritzDectThermostat(_) => false,
             // TODO
             AVMDevice::Other(_) => false,
         }
@@ -115,6 +174,7 @@ impl AVMDevice {
     pub fn is_alert(&self) -> bool {
         match self {
             AVMDevice::FritzDect2XX(_) => false,
+            AVMDevice::FritzDectThermostat(_) => false,
             // TODO
             AVMDevice::Other(xml::Device { alert, .. }) => {
                 alert.as_ref().is_some() && alert.as_ref().unwrap().state
@@ -125,6 +185,7 @@ impl AVMDevice {
     pub fn last_alert_change_epoch(&self) -> u32 {
         match self {
             AVMDevice::FritzDect2XX(_) => 0,
+            AVMDevice::FritzDectThermostat(_) => 0,
             // TODO
             AVMDevice::Other(xml::Device { alert, .. }) => {
                 alert
@@ -142,26 +203,52 @@ impl AVMDevice {
         match self {
             AVMDevice::FritzDect2XX(FritzDect2XX { on: true, .. }) => "on",
             AVMDevice::FritzDect2XX(FritzDect2XX { on: false, .. }) => "off",
+            AVMDevice::FritzDectThermostat(_) => "",
             AVMDevice::Other(_) => "",
         }
     }
 
-    pub fn fetch_device_stats(&self, token: &Token) -> Result<Vec<xml::DeviceStats>> {
-        api::fetch_device_stats(self.id(), token)
+    pub async fn fetch_device_stats(&self, token: &Token) -> Result<Vec<xml::DeviceStats>> {
+        api::fetch_device_stats(self.id(), token).await
+    }
+
+    /// A flattened, serializable view of this device's current state.
+    pub fn snapshot(&self) -> DeviceSnapshot {
+        let (watts, voltage, energy_wh, celsius) = match self {
+            AVMDevice::FritzDect2XX(dev) => (
+                Some(dev.watts),
+                Some(dev.voltage),
+                Some(dev.energy_in_watt_h),
+                Some(dev.celsius),
+            ),
+            AVMDevice::FritzDectThermostat(dev) => (None, None, None, dev.current_celsius()),
+            AVMDevice::Other(_) => (None, None, None, None),
+        };
+        DeviceSnapshot {
+            identifier: self.id().to_string(),
+            name: self.name().to_string(),
+            on: self.is_on(),
+            watts,
+            voltage,
+            energy_wh,
+            celsius,
+            alert: self.is_alert(),
+            stats: None,
+        }
     }
 
-    pub fn turn_on(&mut self, token: &Token) -> Result<()> {
-        api::request(api::Commands::SetSwitchOn, token, Some(self.id()))?;
+    pub async fn turn_on(&mut self, token: &Token) -> Result<()> {
+        api::request(api::Commands::SetSwitchOn, token, Some(self.id())).await?;
         Ok(())
     }
 
-    pub fn turn_off(&mut self, token: &Token) -> Result<()> {
-        api::request(api::Commands::SetSwitchOff, token, Some(self.id()))?;
+    pub async fn turn_off(&mut self, token: &Token) -> Result<()> {
+        api::request(api::Commands::SetSwitchOff, token, Some(self.id())).await?;
         Ok(())
     }
 
-    pub fn toggle(&mut self, token: &Token) -> Result<()> {
-        api::request(api::Commands::SetSwitchToggle, token, Some(self.id()))?;
+    pub async fn toggle(&mut self, token: &Token) -> Result<()> {
+        api::request(api::Commands::SetSwitchToggle, token, Some(self.id())).await?;
         Ok(())
     }
 }
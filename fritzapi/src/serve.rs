@@ -0,0 +1,127 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use tokio::sync::RwLock;
+
+use crate::api::{self, Token};
+use crate::devices::{AVMDevice, DeviceSnapshot};
+use crate::error::Result;
+
+struct ServerState {
+    token: Token,
+    devices: RwLock<Vec<AVMDevice>>,
+}
+
+type SharedState = Arc<ServerState>;
+
+/// Runs the `serve` subcommand: logs in once, caches the [`Token`], keeps the
+/// device list fresh on `refresh_interval`, and exposes it over a small REST
+/// API so a dashboard or automation can poll `ain`s without re-authenticating
+/// on every request.
+///
+/// Routes:
+/// - `GET  /devices`
+/// - `GET  /devices/:ain`
+/// - `GET  /devices/:ain/stats`
+/// - `POST /devices/:ain/on|off|toggle`
+pub async fn run(token: Token, refresh_interval: Duration, addr: SocketAddr) -> Result<()> {
+    let devices = AVMDevice::list(&token).await?;
+    let state: SharedState = Arc::new(ServerState {
+        token,
+        devices: RwLock::new(devices),
+    });
+
+    {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                match AVMDevice::list(&state.token).await {
+                    Ok(devices) => *state.devices.write().await = devices,
+                    Err(err) => log::warn!("[fritz serve] failed to refresh devices: {}", err),
+                }
+            }
+        });
+    }
+
+    let app = Router::new()
+        .route("/devices", get(list_devices))
+        .route("/devices/:ain", get(get_device))
+        .route("/devices/:ain/stats", get(get_device_stats))
+        .route("/devices/:ain/:action", post(control_device))
+        .with_state(state);
+
+    log::info!("[fritz serve] listening on http://{}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn list_devices(State(state): State<SharedState>) -> Json<Vec<DeviceSnapshot>> {
+    let devices = state.devices.read().await;
+    Json(devices.iter().map(AVMDevice::snapshot).collect())
+}
+
+async fn get_device(State(state): State<SharedState>, Path(ain): Path<String>) -> impl IntoResponse {
+    let devices = state.devices.read().await;
+    match devices.iter().find(|dev| dev.id() == ain) {
+        Some(dev) => Json(dev.snapshot()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn get_device_stats(
+    State(state): State<SharedState>,
+    Path(ain): Path<String>,
+) -> impl IntoResponse {
+    let devices = state.devices.read().await;
+    let Some(dev) = devices.iter().find(|dev| dev.id() == ain) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match dev.fetch_device_stats(&state.token).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(err) => {
+            log::warn!("[fritz serve] failed to fetch stats for {}: {}", ain, err);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+async fn control_device(
+    State(state): State<SharedState>,
+    Path((ain, action)): Path<(String, String)>,
+) -> StatusCode {
+    // Only hold the lock long enough to check the device exists - GET
+    // requests would otherwise block for the whole outbound FRITZ!Box call
+    // below, defeating the point of a continuously running gateway. The
+    // background refresh task already keeps `devices` current, so there's
+    // nothing to write back here.
+    {
+        let devices = state.devices.read().await;
+        if !devices.iter().any(|dev| dev.id() == ain) {
+            return StatusCode::NOT_FOUND;
+        }
+    }
+
+    let cmd = match action.as_str() {
+        "on" => api::Commands::SetSwitchOn,
+        "off" => api::Commands::SetSwitchOff,
+        "toggle" => api::Commands::SetSwitchToggle,
+        _ => return StatusCode::BAD_REQUEST,
+    };
+
+    match api::request(cmd, &state.token, Some(&ain)).await {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            log::warn!("[fritz serve] failed to {} {}: {}", action, ain, err);
+            StatusCode::BAD_GATEWAY
+        }
+    }
+}
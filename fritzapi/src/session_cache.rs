@@ -0,0 +1,114 @@
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// How long a cached sid is trusted before we fall back to a full login.
+/// FRITZ!OS itself expires an unused session after ~10 minutes; stay well
+/// under that so we don't hand out a sid that's about to be rejected anyway.
+const MAX_AGE_SECS: u64 = 5 * 60;
+
+/// A FRITZ!OS session persisted to disk, so the CLI doesn't have to perform
+/// a full MD5 challenge-response login on every invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedSession {
+    pub host: String,
+    pub user: String,
+    pub sid: String,
+    pub cached_at_epoch: u64,
+}
+
+pub(crate) fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("fritz-homeautomation").join("session.json"))
+}
+
+/// Loads the cached session for `(host, user)`, if one exists and is still
+/// fresh. Keyed on the user too, not just the host - otherwise a second
+/// account on the same box would silently inherit the first account's sid
+/// and permissions.
+pub(crate) fn load(host: &str, user: &str) -> Option<CachedSession> {
+    let path = cache_path()?;
+    let contents = fs::read_to_string(path).ok()?;
+    let cached: CachedSession = serde_json::from_str(&contents).ok()?;
+    is_usable(&cached, host, user, now_epoch()).then_some(cached)
+}
+
+/// Whether `cached` can be reused for `(host, user)` as of `now_epoch`: it
+/// must be for the same host and user, and no older than [`MAX_AGE_SECS`].
+fn is_usable(cached: &CachedSession, host: &str, user: &str, now_epoch: u64) -> bool {
+    cached.host == host
+        && cached.user == user
+        && now_epoch.saturating_sub(cached.cached_at_epoch) <= MAX_AGE_SECS
+}
+
+/// Persists `sid` for `(host, user)`, mode 0600 so other local users can't
+/// read it.
+pub(crate) fn save(host: &str, user: &str, sid: &str) -> Result<()> {
+    let Some(path) = cache_path() else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let cached = CachedSession {
+        host: host.to_string(),
+        user: user.to_string(),
+        sid: sid.to_string(),
+        cached_at_epoch: now_epoch(),
+    };
+    fs::write(&path, serde_json::to_string(&cached)?)?;
+    #[cfg(unix)]
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+fn now_epoch() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(host: &str, user: &str, cached_at_epoch: u64) -> CachedSession {
+        CachedSession {
+            host: host.to_string(),
+            user: user.to_string(),
+            sid: "abc123".to_string(),
+            cached_at_epoch,
+        }
+    }
+
+    #[test]
+    fn fresh_session_for_matching_host_and_user_is_usable() {
+        let cached = session("fritz.box", "alice", 1_000);
+        assert!(is_usable(&cached, "fritz.box", "alice", 1_000 + MAX_AGE_SECS));
+    }
+
+    #[test]
+    fn expired_session_is_not_usable() {
+        let cached = session("fritz.box", "alice", 1_000);
+        assert!(!is_usable(&cached, "fritz.box", "alice", 1_000 + MAX_AGE_SECS + 1));
+    }
+
+    #[test]
+    fn session_for_a_different_host_is_not_usable() {
+        let cached = session("fritz.box", "alice", 1_000);
+        assert!(!is_usable(&cached, "other.box", "alice", 1_000));
+    }
+
+    #[test]
+    fn session_for_a_different_user_is_not_usable() {
+        // Two accounts on the same box must not share a cached sid - one
+        // user's permissions must never leak into another's session.
+        let cached = session("fritz.box", "alice", 1_000);
+        assert!(!is_usable(&cached, "fritz.box", "bob", 1_000));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::api::{self, Commands, Token};
+use crate::error::Result;
+use crate::fritz_xml as xml;
+
+/// A FRITZ!OS template ("scene"): a saved bundle of device states that can
+/// be applied in one shot, e.g. "all heating to night mode".
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub identifier: String,
+    pub name: String,
+}
+
+impl Template {
+    /// Requests & parses the list of templates via `gettemplatelistinfos`.
+    pub async fn list(token: &Token) -> Result<Vec<Template>> {
+        let xml = api::request(Commands::GetTemplateListInfos, token, None).await?;
+        let templates = xml::parse_template_list_infos(xml)?;
+        Ok(templates
+            .into_iter()
+            .map(|t| Template {
+                identifier: t.identifier,
+                name: t.name,
+            })
+            .collect())
+    }
+
+    /// Applies this template via `applytemplate`.
+    pub async fn apply(&self, token: &Token) -> Result<()> {
+        api::request(Commands::ApplyTemplate, token, Some(&self.identifier)).await?;
+        Ok(())
+    }
+}
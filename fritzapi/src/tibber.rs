@@ -0,0 +1,194 @@
+//! Optional Tibber integration: joins a device's `getbasicdevicestats`
+//! energy series against Tibber's hourly spot prices to produce a cost
+//! breakdown. Only built with `--features tibber`, so users who don't have a
+//! Tibber account aren't forced to pull in its dependencies.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeZone, Timelike, Utc};
+use serde::Deserialize;
+
+use crate::error::{FritzError, Result};
+use crate::fritz_xml::DeviceStats;
+
+const TIBBER_API_URL: &str = "https://api.tibber.com/v1-beta/gql";
+
+/// One hourly spot price, as returned by Tibber's `priceInfo` query.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PricePoint {
+    #[serde(rename = "startsAt")]
+    pub starts_at: DateTime<Utc>,
+    /// Total price including tax, in the home's currency per kWh.
+    pub total: f64,
+}
+
+/// Energy spend for one stats entry: the entry's consumption joined against
+/// the Tibber spot price of the hour it falls in.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HourlyCost {
+    pub hour: DateTime<Utc>,
+    pub energy_wh: u32,
+    pub price_per_kwh: f64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<PriceData>,
+    errors: Option<Vec<GraphQlError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceData {
+    viewer: Viewer,
+}
+
+#[derive(Debug, Deserialize)]
+struct Viewer {
+    home: Home,
+}
+
+#[derive(Debug, Deserialize)]
+struct Home {
+    #[serde(rename = "currentSubscription")]
+    current_subscription: Subscription,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subscription {
+    #[serde(rename = "priceInfo")]
+    price_info: PriceInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceInfo {
+    today: Vec<PricePoint>,
+    tomorrow: Vec<PricePoint>,
+}
+
+/// Fetches today's and tomorrow's hourly spot prices for `home_id` using a
+/// Tibber personal access token.
+pub async fn fetch_hourly_prices(api_token: &str, home_id: &str) -> Result<Vec<PricePoint>> {
+    let query = format!(
+        r#"{{
+          viewer {{
+            home(id: "{home_id}") {{
+              currentSubscription {{
+                priceInfo {{
+                  today {{ total startsAt }}
+                  tomorrow {{ total startsAt }}
+                }}
+              }}
+            }}
+          }}
+        }}"#,
+        home_id = home_id
+    );
+
+    let client = reqwest::Client::new();
+    let response: GraphQlResponse = client
+        .post(TIBBER_API_URL)
+        .bearer_auth(api_token)
+        .json(&serde_json::json!({ "query": query }))
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    if let Some(errors) = response.errors {
+        let message = errors.into_iter().map(|e| e.message).collect::<Vec<_>>().join("; ");
+        return Err(FritzError::TibberError(message));
+    }
+    let data = response
+        .data
+        .ok_or_else(|| FritzError::TibberError("empty response from Tibber".to_string()))?;
+
+    let mut prices = data.viewer.home.current_subscription.price_info.today;
+    prices.extend(data.viewer.home.current_subscription.price_info.tomorrow);
+    Ok(prices)
+}
+
+/// Assigns each entry of `stats` to its hour and joins it against that
+/// hour's [`PricePoint`], producing one [`HourlyCost`] per entry. Entries
+/// that fall in the same hour are not summed; entries whose hour has no
+/// price are dropped.
+pub fn join_costs(stats: &[DeviceStats], prices: &[PricePoint]) -> Vec<HourlyCost> {
+    let prices_by_hour: HashMap<DateTime<Utc>, f64> =
+        prices.iter().map(|p| (truncate_to_hour(p.starts_at), p.total)).collect();
+
+    stats
+        .iter()
+        .filter_map(|stat| {
+            let hour = Utc.timestamp_opt(stat.timestamp as i64, 0).single()?;
+            let hour = truncate_to_hour(hour);
+            let price_per_kwh = *prices_by_hour.get(&hour)?;
+            Some(HourlyCost {
+                hour,
+                energy_wh: stat.energy_wh,
+                price_per_kwh,
+                cost: stat.energy_wh as f64 / 1000.0 * price_per_kwh,
+            })
+        })
+        .collect()
+}
+
+fn truncate_to_hour(ts: DateTime<Utc>) -> DateTime<Utc> {
+    ts.with_minute(0)
+        .and_then(|ts| ts.with_second(0))
+        .and_then(|ts| ts.with_nanosecond(0))
+        .unwrap_or(ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(starts_at: &str, total: f64) -> PricePoint {
+        PricePoint {
+            starts_at: starts_at.parse().unwrap(),
+            total,
+        }
+    }
+
+    fn stat(timestamp: u32, energy_wh: u32) -> DeviceStats {
+        DeviceStats { timestamp, energy_wh }
+    }
+
+    #[test]
+    fn truncate_to_hour_drops_minutes_seconds() {
+        let ts: DateTime<Utc> = "2024-01-01T13:45:30Z".parse().unwrap();
+        assert_eq!(truncate_to_hour(ts).to_rfc3339(), "2024-01-01T13:00:00+00:00");
+    }
+
+    #[test]
+    fn join_costs_matches_stats_to_their_hour_bucket() {
+        let prices = vec![
+            price("2024-01-01T13:00:00Z", 0.30),
+            price("2024-01-01T14:00:00Z", 0.40),
+        ];
+        // 13:15 and 13:50 both fall in the 13:00 bucket.
+        let stats = vec![stat(1_704_114_900, 500), stat(1_704_117_000, 250)];
+
+        let costs = join_costs(&stats, &prices);
+
+        assert_eq!(costs.len(), 2);
+        assert!(costs.iter().all(|c| c.price_per_kwh == 0.30));
+        assert_eq!(costs[0].cost, 500.0 / 1000.0 * 0.30);
+        assert_eq!(costs[1].cost, 250.0 / 1000.0 * 0.30);
+    }
+
+    #[test]
+    fn join_costs_drops_stats_with_no_matching_price() {
+        let prices = vec![price("2024-01-01T13:00:00Z", 0.30)];
+        // An hour with no matching price point is silently skipped, not
+        // joined against the wrong price.
+        let stats = vec![stat(1_704_121_200, 500)]; // 2024-01-01T15:00:00Z
+
+        assert!(join_costs(&stats, &prices).is_empty());
+    }
+}
@@ -1,6 +1,68 @@
 use clap::{App, Arg, ArgMatches};
-use fritz_homeautomation::{api, daylight};
+use fritz_homeautomation::daylight;
+use fritzapi::api;
+use fritzapi::config::Config;
+use fritzapi::devices::{AVMDevice, DeviceSnapshot};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
+
+/// Quotes a CSV field per RFC 4180 if it contains a comma, quote, or
+/// newline - FRITZ!Box device names are free text and commonly contain a
+/// comma (e.g. "Living Room, Lamp"), which would otherwise corrupt columns.
+fn csv_field(value: &str) -> String {
+    if value.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints device snapshots as a CSV table.
+fn print_csv(snapshots: &[DeviceSnapshot]) {
+    println!("identifier,name,on,watts,voltage,energy_wh,celsius,alert");
+    for s in snapshots {
+        println!(
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&s.identifier),
+            csv_field(&s.name),
+            s.on,
+            s.watts.map(|v| v.to_string()).unwrap_or_default(),
+            s.voltage.map(|v| v.to_string()).unwrap_or_default(),
+            s.energy_wh.map(|v| v.to_string()).unwrap_or_default(),
+            s.celsius.map(|v| v.to_string()).unwrap_or_default(),
+            s.alert,
+        );
+    }
+}
+
+/// Prints the `--stats` fields of a [`DeviceSnapshot`] under a plain-format
+/// device listing, indented under the device's own line. Without this, `list
+/// --stats` (the default, non-json/csv invocation) fetched stats over the
+/// network but threw the result away.
+fn print_plain_stats(snapshot: &DeviceSnapshot) {
+    if let Some(watts) = snapshot.watts {
+        println!("  watts={:.3}", watts);
+    }
+    if let Some(voltage) = snapshot.voltage {
+        println!("  voltage={:.3}", voltage);
+    }
+    if let Some(celsius) = snapshot.celsius {
+        println!("  celsius={:.1}", celsius);
+    }
+    if let Some(energy_wh) = snapshot.energy_wh {
+        println!("  energy_wh={}", energy_wh);
+    }
+    match &snapshot.stats {
+        Some(stats) if !stats.is_empty() => {
+            for stat in stats {
+                println!("  stat: timestamp={} energy_wh={}", stat.timestamp, stat.energy_wh);
+            }
+        }
+        _ => println!("  (no stats available)"),
+    }
+}
 
 fn valid_coord(val: String) -> Result<(), String> {
     val.parse::<f64>()
@@ -20,6 +82,14 @@ fn valid_shift(arg: String) -> Result<(), String> {
         .ok_or("Not a valid time shift".to_string())
 }
 
+fn valid_refresh_seconds(arg: String) -> Result<(), String> {
+    match arg.parse::<u64>() {
+        Ok(0) => Err("--refresh-seconds must be greater than 0".to_string()),
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 fn parse_duration(arg: &str) -> Option<chrono::Duration> {
     let sign = arg.starts_with("-");
     let input = if sign { &arg[1..] } else { arg };
@@ -76,105 +146,344 @@ fn daylight(args: &ArgMatches) {
     daylight::print_daylight_times(location, from_date, to_date, shift_from, shift_to);
 }
 
-fn list(args: &ArgMatches) -> anyhow::Result<()> {
-    let user = args.value_of("user").unwrap();
-    let password = args.value_of("password").unwrap();
-    let ain = args.value_of("ain");
+/// Resolves a value from a CLI flag first, falling back to the config file.
+fn resolve<'a>(args: &'a ArgMatches, config_value: &'a Option<String>, flag: &str) -> Option<&'a str> {
+    args.value_of(flag).or(config_value.as_deref())
+}
+
+/// The CLI itself stays synchronous; this is the one place that spins up a
+/// runtime to drive the async `fritzapi` calls to completion.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Runtime::new()
+        .expect("failed to start tokio runtime")
+        .block_on(future)
+}
+
+fn list(args: &ArgMatches, config: &Config) -> anyhow::Result<()> {
+    let host = resolve(args, &config.host, "host").unwrap_or_else(|| config.host());
+    let user = resolve(args, &config.user, "user")
+        .ok_or_else(|| anyhow::anyhow!("no --user given and none set in the config file"))?;
+    let password = resolve(args, &config.password, "password")
+        .ok_or_else(|| anyhow::anyhow!("no --password given and none set in the config file"))?;
+    let ain = resolve(args, &config.ain, "ain");
     let show_stats = args.is_present("stats");
+    let format = args.value_of("format").unwrap_or("plain");
 
-    let sid = api::get_sid(&user, &password)?;
-    let devices: Vec<_> = api::device_infos_avm(&sid)?;
+    block_on(async {
+        let token = api::get_token(host, user, password).await?;
+        let mut devices: Vec<_> = AVMDevice::list(&token).await?;
 
-    if let Some(ain) = ain {
-        let device = match devices.into_iter().find(|dev| dev.id() == ain) {
-            None => {
+        if let Some(ain) = ain {
+            devices.retain(|dev| dev.id() == ain);
+            if devices.is_empty() {
                 return Err(anyhow::anyhow!("Cannot find device with ain {:?}", ain));
             }
-            Some(device) => device,
-        };
-        device.print_info(show_stats, Some(&sid))?;
-        return Ok(());
-    }
+        }
 
-    println!("found {} devices", devices.len());
+        let mut snapshots: Vec<DeviceSnapshot> = devices.iter().map(AVMDevice::snapshot).collect();
 
-    for device in devices {
-        device.print_info(show_stats, Some(&sid))?;
-    }
+        if show_stats {
+            // Poll every device's stats concurrently instead of one slow
+            // round-trip after another.
+            let stats = futures::future::join_all(
+                devices.iter().map(|dev| dev.fetch_device_stats(&token)),
+            )
+            .await;
+            for (snapshot, stats) in snapshots.iter_mut().zip(stats) {
+                snapshot.stats = stats.ok();
+            }
+        }
+
+        match format {
+            "json" => println!("{}", serde_json::to_string_pretty(&snapshots)?),
+            "csv" => print_csv(&snapshots),
+            _ => {
+                println!("found {} devices", devices.len());
+                for (device, snapshot) in devices.iter().zip(&snapshots) {
+                    println!("{}", device);
+                    if show_stats {
+                        print_plain_stats(snapshot);
+                    }
+                }
+            }
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
-fn switch(args: &ArgMatches) -> anyhow::Result<()> {
-    let user = args.value_of("user").unwrap();
-    let password = args.value_of("password").unwrap();
-    let ain = args.value_of("ain").unwrap();
+fn switch(args: &ArgMatches, config: &Config) -> anyhow::Result<()> {
+    let host = resolve(args, &config.host, "host").unwrap_or_else(|| config.host());
+    let user = resolve(args, &config.user, "user")
+        .ok_or_else(|| anyhow::anyhow!("no --user given and none set in the config file"))?;
+    let password = resolve(args, &config.password, "password")
+        .ok_or_else(|| anyhow::anyhow!("no --password given and none set in the config file"))?;
+    let ain = resolve(args, &config.ain, "ain")
+        .ok_or_else(|| anyhow::anyhow!("no --ain given and none set in the config file"))?;
     let toggle = args.is_present("toggle");
     let on = args.is_present("on");
     let off = args.is_present("off");
 
-    let sid = api::get_sid(&user, &password)?;
-    let devices: Vec<_> = api::device_infos_avm(&sid)?;
+    block_on(async {
+        let token = api::get_token(host, user, password).await?;
+        let devices: Vec<_> = AVMDevice::list(&token).await?;
 
-    let mut device = match devices.into_iter().find(|dev| dev.id() == ain) {
-        None => {
-            return Err(anyhow::anyhow!("Cannot find device with ain {:?}", ain));
+        let mut device = match devices.into_iter().find(|dev| dev.id() == ain) {
+            None => {
+                return Err(anyhow::anyhow!("Cannot find device with ain {:?}", ain));
+            }
+            Some(device) => device,
+        };
+
+        if toggle {
+            device.toggle(&token).await?;
+        } else if on {
+            device.turn_on(&token).await?;
+        } else if off {
+            device.turn_off(&token).await?;
         }
-        Some(device) => device,
-    };
 
-    if toggle {
-        device.toggle(&sid)?;
-    } else if on {
-        device.turn_on(&sid)?;
-    } else if off {
-        device.turn_off(&sid)?;
+        Ok(())
+    })
+}
+
+fn serve(args: &ArgMatches, config: &Config) -> anyhow::Result<()> {
+    let host = resolve(args, &config.host, "host").unwrap_or_else(|| config.host());
+    let user = resolve(args, &config.user, "user")
+        .ok_or_else(|| anyhow::anyhow!("no --user given and none set in the config file"))?;
+    let password = resolve(args, &config.password, "password")
+        .ok_or_else(|| anyhow::anyhow!("no --password given and none set in the config file"))?;
+    let addr: SocketAddr = args.value_of("listen").unwrap().parse()?;
+    let refresh_seconds: u64 = args.value_of("refresh-seconds").unwrap().parse()?;
+    if refresh_seconds == 0 {
+        return Err(anyhow::anyhow!("--refresh-seconds must be greater than 0"));
+    }
+    let refresh_interval = Duration::from_secs(refresh_seconds);
+
+    block_on(async {
+        let token = api::get_token(host, user, password).await?;
+        fritzapi::serve::run(token, refresh_interval, addr).await?;
+        Ok(())
+    })
+}
+
+#[cfg(feature = "tibber")]
+fn cost(args: &ArgMatches, config: &Config) -> anyhow::Result<()> {
+    let host = resolve(args, &config.host, "host").unwrap_or_else(|| config.host());
+    let user = resolve(args, &config.user, "user")
+        .ok_or_else(|| anyhow::anyhow!("no --user given and none set in the config file"))?;
+    let password = resolve(args, &config.password, "password")
+        .ok_or_else(|| anyhow::anyhow!("no --password given and none set in the config file"))?;
+    let ain = args.value_of("ain").unwrap();
+    let from = chrono::NaiveDate::parse_from_str(args.value_of("from").unwrap(), "%Y-%m-%d")?;
+    let to = chrono::NaiveDate::parse_from_str(args.value_of("to").unwrap(), "%Y-%m-%d")?;
+
+    // Tibber's `priceInfo` query only ever exposes today's and tomorrow's
+    // hourly prices - anything outside that window would silently join
+    // against nothing and print a bogus "total spend: 0.00" instead of
+    // failing loudly.
+    let today = chrono::Utc::now().date_naive();
+    let tomorrow = today + chrono::Duration::days(1);
+    if from < today || to > tomorrow {
+        return Err(anyhow::anyhow!(
+            "Tibber only exposes prices for today and tomorrow ({} to {}); --from/--to must fall within that range",
+            today,
+            tomorrow
+        ));
     }
 
-    Ok(())
+    let tibber_token = std::env::var("TIBBER_TOKEN")
+        .map_err(|_| anyhow::anyhow!("TIBBER_TOKEN environment variable is required for `fritz cost`"))?;
+    let tibber_home_id = std::env::var("TIBBER_HOME_ID")
+        .map_err(|_| anyhow::anyhow!("TIBBER_HOME_ID environment variable is required for `fritz cost`"))?;
+
+    block_on(async {
+        let token = api::get_token(host, user, password).await?;
+        let devices = AVMDevice::list(&token).await?;
+        let device = devices
+            .into_iter()
+            .find(|dev| dev.id() == ain)
+            .ok_or_else(|| anyhow::anyhow!("Cannot find device with ain {:?}", ain))?;
+
+        let stats = device.fetch_device_stats(&token).await?;
+        let prices = fritzapi::tibber::fetch_hourly_prices(&tibber_token, &tibber_home_id).await?;
+        let costs: Vec<_> = fritzapi::tibber::join_costs(&stats, &prices)
+            .into_iter()
+            .filter(|c| {
+                let date = c.hour.date_naive();
+                date >= from && date <= to
+            })
+            .collect();
+
+        let total: f64 = costs.iter().map(|c| c.cost).sum();
+        let avg_price = if costs.is_empty() {
+            0.0
+        } else {
+            costs.iter().map(|c| c.price_per_kwh).sum::<f64>() / costs.len() as f64
+        };
+        println!("total spend: {:.2}", total);
+        println!("average price: {:.4} / kWh", avg_price);
+
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "tibber"))]
+fn cost(_args: &ArgMatches, _config: &Config) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "fritz was built without the `tibber` feature; rebuild with --features tibber to use `fritz cost`"
+    ))
+}
+
+fn templates(args: &ArgMatches, config: &Config) -> anyhow::Result<()> {
+    let host = resolve(args, &config.host, "host").unwrap_or_else(|| config.host());
+    let user = resolve(args, &config.user, "user")
+        .ok_or_else(|| anyhow::anyhow!("no --user given and none set in the config file"))?;
+    let password = resolve(args, &config.password, "password")
+        .ok_or_else(|| anyhow::anyhow!("no --password given and none set in the config file"))?;
+
+    block_on(async {
+        let token = api::get_token(host, user, password).await?;
+
+        match args.subcommand() {
+            ("list", Some(_)) => {
+                for template in fritzapi::templates::Template::list(&token).await? {
+                    println!("{} {:?}", template.identifier, template.name);
+                }
+            }
+            ("apply", Some(apply_args)) => {
+                let id = apply_args.value_of("id").unwrap();
+                let template = fritzapi::templates::Template::list(&token)
+                    .await?
+                    .into_iter()
+                    .find(|t| t.identifier == id)
+                    .ok_or_else(|| anyhow::anyhow!("Cannot find template with id {:?}", id))?;
+                template.apply(&token).await?;
+            }
+            _ => return Err(anyhow::anyhow!("expected `templates list` or `templates apply --id ...`")),
+        }
+
+        Ok(())
+    })
 }
 
 // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
 fn main() {
-    let user = Arg::with_name("user")
-        .long("user")
-        .short("u")
+    let host = Arg::with_name("host")
+        .long("host")
         .takes_value(true)
-        .required(true);
+        .help("The FRITZ!Box host to talk to (default: the config file's `host`, or fritz.box).");
+
+    let user = Arg::with_name("user").long("user").short("u").takes_value(true);
 
     let password = Arg::with_name("password")
         .long("password")
         .short("p")
-        .takes_value(true)
-        .required(true);
+        .takes_value(true);
 
     let ain = Arg::with_name("ain")
         .long("ain")
         .takes_value(true)
-        .required(true)
+        .required(false)
         .help("The device identifier of the device to query / control.");
 
+    let config_arg = Arg::with_name("config")
+        .long("config")
+        .takes_value(true)
+        .global(true)
+        .help("Path to a config file (default: ~/.config/fritz-homeautomation/config.toml)");
+
     let mut app = App::new(env!("CARGO_PKG_NAME"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .version(env!("CARGO_PKG_VERSION"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(config_arg)
         .subcommand(
             App::new("list")
+                .arg(host.clone())
                 .arg(user.clone())
                 .arg(password.clone())
-                .arg(ain.clone().required(false))
-                .arg(Arg::with_name("stats").long("stats")),
+                .arg(ain.clone())
+                .arg(Arg::with_name("stats").long("stats"))
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["plain", "json", "csv"])
+                        .default_value("plain"),
+                ),
         )
         .subcommand(
             App::new("switch")
-                .arg(user)
-                .arg(password)
-                .arg(ain.clone().required(true))
+                .arg(host.clone())
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(ain.clone().required(false))
                 .arg(Arg::with_name("toggle").long("toggle"))
                 .arg(Arg::with_name("on").long("on"))
                 .arg(Arg::with_name("off").long("off")),
         )
+        .subcommand(
+            App::new("serve")
+                .arg(host.clone())
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:8080")
+                        .help("Address to bind the REST API to."),
+                )
+                .arg(
+                    Arg::with_name("refresh-seconds")
+                        .long("refresh-seconds")
+                        .takes_value(true)
+                        .default_value("30")
+                        .validator(valid_refresh_seconds)
+                        .help("How often to refresh the cached device list."),
+                ),
+        )
+        .subcommand(
+            App::new("cost")
+                .about("Prints the electricity cost of a device's energy usage, using Tibber spot prices. Requires the `tibber` feature.")
+                .arg(host.clone())
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(ain.required(true))
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(valid_date),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(valid_date),
+                ),
+        )
+        .subcommand(
+            App::new("templates")
+                .about("Lists or applies FRITZ!OS templates (scenes).")
+                .arg(host)
+                .arg(user)
+                .arg(password)
+                .subcommand(App::new("list"))
+                .subcommand(
+                    App::new("apply").arg(
+                        Arg::with_name("id")
+                            .long("id")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The identifier of the template to apply."),
+                    ),
+                ),
+        )
         .subcommand(
             App::new("daylight")
                 .help("Prints the daylight times at a specific location. On MacOS will try to use the corelocation API if no latitude/longitude is specified.")
@@ -210,6 +519,13 @@ fn main() {
 
     let args = app.clone().get_matches();
 
+    let config_path = args
+        .value_of("config")
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+        .expect("could not determine a config file path; pass --config explicitly");
+    let config = Config::load(&config_path).unwrap();
+
     let cmd = match args.subcommand {
         None => {
             app.print_help().unwrap();
@@ -224,10 +540,19 @@ fn main() {
             daylight(args);
         }
         "list" => {
-            list(args.subcommand_matches("list").unwrap()).unwrap();
+            list(args.subcommand_matches("list").unwrap(), &config).unwrap();
         }
         "switch" => {
-            switch(args.subcommand_matches("switch").unwrap()).unwrap();
+            switch(args.subcommand_matches("switch").unwrap(), &config).unwrap();
+        }
+        "serve" => {
+            serve(args.subcommand_matches("serve").unwrap(), &config).unwrap();
+        }
+        "cost" => {
+            cost(args.subcommand_matches("cost").unwrap(), &config).unwrap();
+        }
+        "templates" => {
+            templates(args.subcommand_matches("templates").unwrap(), &config).unwrap();
         }
         _ => {
             app.print_help().unwrap();